@@ -1,62 +1,203 @@
 use std::io::prelude::*;
+use std::io::ErrorKind;
 use std::net::TcpListener;
 use std::net::TcpStream;
 use std::fs;
 use std::thread;
 use std::time::Duration;
-use hello_webserver::ThreadPool;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+use hello_webserver::config::ServerConfig;
+use hello_webserver::http::{Request, Response, Router};
+use hello_webserver::{static_files, ThreadPool};
+
+fn build_router(root: &str) -> Router {
+    let mut router = Router::new();
+    let root = root.to_string();
+
+    // these three used to `fs::read_to_string(...).unwrap()`, panicking the worker thread on
+    // a missing file - and since ThreadPool never respawns a dead worker, a single missing
+    // hello.html could permanently take workers out of the pool one request at a time until
+    // none were left. read_page maps read errors the same way static_files::serve does, so a
+    // missing file becomes a 404 and an unreadable one a 500 instead of a worker-killing panic.
+    {
+        let root = root.clone();
+        router.add("GET", "/", move |_request| read_page(&root, "hello.html"));
+    }
+    {
+        let root = root.clone();
+        router.add("GET", "/sleep", move |_request| {
+            thread::sleep(Duration::from_secs(5));
+            read_page(&root, "sleepy.html")
+        });
+    }
+    {
+        let root = root.clone();
+        router.add("GET", "/stop", move |_request| read_page(&root, "hello.html"));
+    }
+
+    // anything that isn't one of the three demo routes above is looked up as a static file
+    // under `root` instead of the old flat "everything else is a 404" fallback
+    router.set_fallback(move |request| static_files::serve(&root, request));
+
+    router
+}
+
+/// Reads `root/name` as the body of a 200, or a 404/500 if that fails - the same
+/// read-and-map-errors shape as `static_files::serve`, for the handful of demo routes that
+/// read a fixed file instead of one derived from the request path.
+fn read_page(root: &str, name: &str) -> Response {
+    match fs::read_to_string(format!("{}/{}", root, name)) {
+        Ok(contents) => Response::ok(contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Response::not_found(),
+        Err(_) => Response::internal_error(),
+    }
+}
 
 fn main() {
-    let listener = TcpListener::bind("127.0.0.1:7878").unwrap();
-    let pool = ThreadPool::new(4);
+    let config = ServerConfig::from_args(std::env::args());
+
+    let listener = TcpListener::bind(config.address()).unwrap();
+    // non-blocking so the accept loop below can poll `shutting_down` instead of sitting
+    // inside accept() forever with nothing to check it against
+    listener.set_nonblocking(true).unwrap();
+
+    let pool = match ThreadPool::build(config.threads) {
+        Ok(pool) => pool,
+        Err(e) => {
+            eprintln!("Failed to start server: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let router = Arc::new(build_router(&config.root));
+
+    // shared between the Ctrl-C handler and the accept loop: set once and only ever read
+    // after that, so a plain AtomicBool is enough, no Mutex needed
+    let shutting_down = Arc::new(AtomicBool::new(false));
+    {
+        let shutting_down = Arc::clone(&shutting_down);
+        ctrlc::set_handler(move || {
+            println!("Received Ctrl-C, finishing in-flight requests and shutting down.");
+            shutting_down.store(true, Ordering::SeqCst);
+        })
+        .expect("Error setting Ctrl-C handler");
+    }
 
     //The incoming method on TcpListener returns an iterator that gives us a sequence of streams
     //(TcpStream iterator)
     //Iterating over incoming is equivalent to calling TcpListener::accept in a loop.
-    for stream in listener.incoming().take(2) {
-        let stream = stream.unwrap();
+    //We used to stop after take(2) just to demo shutdown; now the loop runs until Ctrl-C
+    //flips `shutting_down`, then falls out and lets ThreadPool's Drop impl drain whatever
+    //jobs are already queued via the existing Message::Terminate protocol.
+    //
+    // each connection's result.recv() used to be called right here in the accept loop,
+    // which blocked accept() from moving on to the next connection until the current one's
+    // handler had fully finished - that serialized every request through one at a time
+    // (hit /sleep and everything else queued behind it for 5s) regardless of pool size.
+    // `pending` holds one Receiver per in-flight connection instead, and we only ever
+    // try_recv() them - never block on them - so the loop keeps accepting while handlers
+    // run concurrently across the pool.
+    let mut pending: Vec<Receiver<ControlSignal>> = Vec::new();
+
+    for stream in listener.incoming() {
+        if shutting_down.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                // poll_pending used to only run right after accepting a connection, so
+                // /stop's signal sat unnoticed in its Receiver until some unrelated future
+                // connection happened to be accepted - if none ever came, the process ran
+                // forever. This WouldBlock branch is the idle-loop tick that fires every
+                // 100ms regardless of traffic, so checking here means shutdown is noticed
+                // even with no further connections at all.
+                poll_pending(&mut pending, &shutting_down);
+                thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+            Err(e) => {
+                println!("Connection failed: {}", e);
+                continue;
+            }
+        };
+        stream.set_nonblocking(false).unwrap();
 
         println!("Connection established!");
         //handle_connection(stream);
         //
-        pool.execute(|| { //should work like thread::spawn
-            handle_connection(stream);
-        });
+        let router = Arc::clone(&router);
+        pending.push(pool.execute_with_result(move || handle_connection(stream, &router)));
+
+        poll_pending(&mut pending, &shutting_down);
     }
 
     println!("Shutting down.");
 }
 
-fn handle_connection(mut stream: TcpStream) {
-    let mut buffer = [0; 1024];// buffer is an array with only 1024 bytes in size; which is enough for basic request
-    // that syntax means [0, 0 ... * 1024 times]
-
-    // read is from std::io::Read, becoz TcpStream implemented it
-    // TcpStream's read here might change it's internal state, hence need to be mut
-    stream.read(&mut buffer).unwrap();
-
+// Checks every in-flight connection's result without blocking on any of them, dropping the
+// finished ones and setting `shutting_down` if any of them was the /stop route. Called both
+// right after accepting a connection and on the idle-loop's WouldBlock tick, so a /stop hit
+// is noticed on the next ~100ms tick instead of only whenever another connection happens in.
+fn poll_pending(pending: &mut Vec<Receiver<ControlSignal>>, shutting_down: &AtomicBool) {
+    pending.retain(|result| match result.try_recv() {
+        Ok(ControlSignal::Stop) => {
+            println!("/stop route hit, shutting down.");
+            shutting_down.store(true, Ordering::SeqCst);
+            false
+        }
+        Ok(ControlSignal::Continue) => false,
+        Err(TryRecvError::Empty) => true,
+        Err(TryRecvError::Disconnected) => false,
+    });
+}
 
-    // from_utf8_lossy takes a slice of bytes &[u8]
-    // The “lossy” part of the name indicates the behavior of this function when it sees an invalid UTF-8 sequence: it will replace the invalid sequence with �, the U+FFFD REPLACEMENT CHARACTER
-    //println!("Request: {}", String::from_utf8_lossy(&buffer[..]));
+// What a handler hands back to the accept loop once it's done with a connection. Plain
+// `execute` can't express this since its closures return nothing; `execute_with_result`
+// is what lets handle_connection report "begin shutdown" without the loop having to peek
+// at the request itself.
+enum ControlSignal {
+    Continue,
+    Stop,
+}
 
-    let get = b"GET / HTTP/1.1\r\n"; //byte string
-    let sleep = b"GET /sleep HTTP/1.1\r\n";
+fn handle_connection(mut stream: TcpStream, router: &Router) -> ControlSignal {
+    // used to be a fixed [0; 1024] array matched with starts_with against a couple of
+    // hardcoded request lines; Request::from_stream instead grows its buffer until it has
+    // read the full header block (and the body, per Content-Length), so it can't truncate
+    // requests longer than 1024 bytes the way the old code did
+    let request = match Request::from_stream(&mut stream) {
+        Ok(request) => request,
+        Err(e) if e.kind() == ErrorKind::InvalidData => {
+            // from_stream's own size guards (a Content-Length over MAX_BODY_SIZE or a header
+            // block over MAX_HEADER_SIZE), both answered with a 413; tell the client why
+            // instead of just dropping the connection
+            println!("Rejecting request: {}", e);
+            let _ = stream.write_all(&Response::payload_too_large().to_bytes());
+            let _ = stream.flush();
+            return ControlSignal::Continue;
+        }
+        Err(e) => {
+            println!("Failed to read request: {}", e);
+            return ControlSignal::Continue;
+        }
+    };
 
-    let (status_line, filename) = if buffer.starts_with(get) {
-        ("HTTP/1.1 200 OK\r\n\r\n", "hello.html")
-    } else if buffer.starts_with(sleep) {
-        thread::sleep(Duration::from_secs(5));
-        ("HTTP/1.1 200 OK\r\n\r\n", "sleepy.html")
+    // the router has no notion of "begin shutdown"; that stays handle_connection's job, same
+    // as before the router existed, so it can hand the signal back through execute_with_result
+    let signal = if request.method == "GET" && request.path == "/stop" {
+        ControlSignal::Stop
     } else {
-        ("HTTP/1.1 404 NOT FOUND\r\n\r\n", "404.html")
+        ControlSignal::Continue
     };
 
-    let contents = fs::read_to_string(filename).unwrap();
-
-    let response = format!("{}{}", status_line, contents);
+    let response = router.dispatch(&request);
 
-    stream.write(response.as_bytes()).unwrap();
+    stream.write_all(&response.to_bytes()).unwrap();
     stream.flush().unwrap();
+
+    signal
 }
 
@@ -0,0 +1,124 @@
+// The bind host, port, worker count and served-file directory used to be hardcoded in
+// main (127.0.0.1:7878, 4 threads, current working directory). ServerConfig pulls all of
+// that into one place that can be built from CLI args instead, so the server is deployable
+// without recompiling it.
+
+/// Runtime configuration for the server: where to listen, how many worker threads to run,
+/// and which directory to resolve requested file paths against.
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    pub threads: usize,
+    pub root: String,
+}
+
+impl ServerConfig {
+    /// Parse `--host`, `--port`, `--threads` and `--root` out of an argument iterator
+    /// (typically `std::env::args()`), falling back to `ServerConfig::default()` for
+    /// anything not passed. Unrecognized flags and malformed values are ignored rather
+    /// than treated as a hard error, so a typo'd flag just falls back to the default.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> ServerConfig {
+        let mut config = ServerConfig::default();
+        let mut args = args.into_iter().skip(1); // skip argv[0], the binary path
+
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "--host" => {
+                    if let Some(value) = args.next() {
+                        config.host = value;
+                    }
+                }
+                "--port" => {
+                    if let Some(value) = args.next() {
+                        if let Ok(port) = value.parse() {
+                            config.port = port;
+                        }
+                    }
+                }
+                "--threads" => {
+                    if let Some(value) = args.next() {
+                        if let Ok(threads) = value.parse() {
+                            config.threads = threads;
+                        }
+                    }
+                }
+                "--root" => {
+                    if let Some(value) = args.next() {
+                        config.root = value;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        config
+    }
+
+    /// The `host:port` string to hand to `TcpListener::bind`.
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 7878,
+            threads: 4,
+            root: ".".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_args(flags: &[&str]) -> ServerConfig {
+        let mut args = vec!["hello_webserver".to_string()];
+        args.extend(flags.iter().map(|flag| flag.to_string()));
+        ServerConfig::from_args(args)
+    }
+
+    #[test]
+    fn defaults_when_no_flags_given() {
+        let config = from_args(&[]);
+
+        assert_eq!(config.host, "127.0.0.1");
+        assert_eq!(config.port, 7878);
+        assert_eq!(config.threads, 4);
+        assert_eq!(config.root, ".");
+    }
+
+    #[test]
+    fn parses_all_flags() {
+        let config = from_args(&["--host", "0.0.0.0", "--port", "9090", "--threads", "8", "--root", "/srv/www"]);
+
+        assert_eq!(config.host, "0.0.0.0");
+        assert_eq!(config.port, 9090);
+        assert_eq!(config.threads, 8);
+        assert_eq!(config.root, "/srv/www");
+    }
+
+    #[test]
+    fn unrecognized_flag_is_ignored() {
+        let config = from_args(&["--bogus", "value", "--port", "9090"]);
+
+        assert_eq!(config.port, 9090);
+    }
+
+    #[test]
+    fn malformed_value_falls_back_to_the_default() {
+        let config = from_args(&["--port", "not-a-number"]);
+
+        assert_eq!(config.port, 7878);
+    }
+
+    #[test]
+    fn address_combines_host_and_port() {
+        let config = from_args(&["--host", "0.0.0.0", "--port", "9090"]);
+
+        assert_eq!(config.address(), "0.0.0.0:9090");
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::TcpStream;
+
+// `handle_connection` used to byte-match a handful of fixed request lines (`GET / HTTP/1.1\r\n`)
+// against a 1024-byte array, which silently truncated anything longer and couldn't read
+// headers or a body at all. `Request::from_stream` instead grows a buffer until it has seen
+// the blank line that ends the headers, and honors `Content-Length` for whatever body follows.
+
+// a client's Content-Length is just a claim, not a fact - trusting it unconditionally lets
+// anyone force a multi-gigabyte allocation per connection before we've even tried to read
+// that many bytes. Past this, from_stream refuses the request instead of allocating.
+const MAX_BODY_SIZE: usize = 10 * 1024 * 1024; // 10 MiB
+
+// the header-scan loop below grows `raw` for as long as it takes to find `\r\n\r\n`, so a
+// client that never sends the terminator (or sends an enormous header block) could make it
+// grow unbounded - the same unchecked-client-input problem MAX_BODY_SIZE guards against on
+// the body side. Real request headers fit comfortably in a few KB; past this, give up instead
+// of growing `raw` forever.
+const MAX_HEADER_SIZE: usize = 8 * 1024; // 8 KiB
+
+/// A parsed HTTP request: method, path, query string, headers and body.
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Read and parse one HTTP request off `stream`.
+    ///
+    /// Grows its read buffer a chunk at a time (instead of a fixed-size array) until the
+    /// `\r\n\r\n` header terminator shows up, then reads exactly `Content-Length` more bytes
+    /// for the body.
+    pub fn from_stream(stream: &mut TcpStream) -> io::Result<Request> {
+        let mut reader = BufReader::new(stream);
+        let mut raw = Vec::new();
+
+        let header_end = loop {
+            if let Some(pos) = find_subslice(&raw, b"\r\n\r\n") {
+                break pos + 4;
+            }
+
+            if raw.len() >= MAX_HEADER_SIZE {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("request headers exceed the {} byte maximum", MAX_HEADER_SIZE),
+                ));
+            }
+
+            let mut chunk = [0u8; 512];
+            let n = reader.read(&mut chunk)?;
+            if n == 0 {
+                // connection closed before we ever saw a full header block
+                break raw.len();
+            }
+            raw.extend_from_slice(&chunk[..n]);
+        };
+
+        let (head, overflow) = raw.split_at(header_end.min(raw.len()));
+        let head = String::from_utf8_lossy(head);
+        let mut lines = head.lines();
+
+        let request_line = lines.next().unwrap_or("");
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or("GET").to_string();
+        let full_path = parts.next().unwrap_or("/").to_string();
+        let (path, query) = match full_path.split_once('?') {
+            Some((path, query)) => (path.to_string(), query.to_string()),
+            None => (full_path, String::new()),
+        };
+
+        let mut headers = HashMap::new();
+        for line in lines {
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+            }
+        }
+
+        let content_length: usize = headers
+            .get("content-length")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0);
+
+        if content_length > MAX_BODY_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Content-Length {} exceeds the {} byte maximum", content_length, MAX_BODY_SIZE),
+            ));
+        }
+
+        // `overflow` is whatever we already read past the header terminator while growing
+        // `raw` above - it belongs to the body and must not be read a second time
+        let mut body = overflow.to_vec();
+        if body.len() < content_length {
+            let mut rest = vec![0u8; content_length - body.len()];
+            reader.read_exact(&mut rest)?;
+            body.extend_from_slice(&rest);
+        } else {
+            body.truncate(content_length);
+        }
+
+        Ok(Request { method, path, query, headers, body })
+    }
+}
+
+fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// What a handler sends back; `Router::dispatch` always returns one of these, even for the
+/// fallback 404, so `handle_connection` has exactly one thing to serialize onto the stream.
+///
+/// `body` is bytes rather than `String` so a handler (e.g. `static_files::serve`) can send
+/// back a binary asset without going through `fs::read_to_string`, which panics on anything
+/// that isn't valid UTF-8.
+pub struct Response {
+    pub status_line: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn ok(body: impl Into<Vec<u8>>) -> Response {
+        let mut headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), "text/html; charset=utf-8".to_string());
+
+        Response {
+            status_line: "HTTP/1.1 200 OK".to_string(),
+            headers,
+            body: body.into(),
+        }
+    }
+
+    pub fn not_found() -> Response {
+        Response {
+            status_line: "HTTP/1.1 404 NOT FOUND".to_string(),
+            headers: HashMap::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn internal_error() -> Response {
+        Response {
+            status_line: "HTTP/1.1 500 INTERNAL SERVER ERROR".to_string(),
+            headers: HashMap::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn payload_too_large() -> Response {
+        Response {
+            status_line: "HTTP/1.1 413 PAYLOAD TOO LARGE".to_string(),
+            headers: HashMap::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: impl Into<String>) -> Response {
+        self.headers.insert(name.to_string(), value.into());
+        self
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut head = format!("{}\r\n", self.status_line);
+        head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+
+        let mut bytes = head.into_bytes();
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+}
+
+type Handler = Box<dyn Fn(&Request) -> Response + Send + Sync + 'static>;
+
+/// Registers `(method, path) -> handler` pairs and dispatches requests to them, falling back
+/// to `fallback` when nothing matches - by default a plain 404, but `set_fallback` lets
+/// something like `static_files::serve` take over unmatched paths instead. Replaces the
+/// `starts_with(b"GET / HTTP/1.1\r\n")` chain in `handle_connection` with something that can
+/// grow past two or three hardcoded routes.
+pub struct Router {
+    routes: Vec<(String, String, Handler)>,
+    fallback: Handler,
+}
+
+impl Default for Router {
+    fn default() -> Router {
+        Router::new()
+    }
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router {
+            routes: Vec::new(),
+            fallback: Box::new(|_request| Response::not_found()),
+        }
+    }
+
+    pub fn add<F>(&mut self, method: &str, path: &str, handler: F)
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.routes.push((method.to_string(), path.to_string(), Box::new(handler)));
+    }
+
+    pub fn set_fallback<F>(&mut self, handler: F)
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.fallback = Box::new(handler);
+    }
+
+    pub fn dispatch(&self, request: &Request) -> Response {
+        for (method, path, handler) in &self.routes {
+            if method == &request.method && path == &request.path {
+                return handler(request);
+            }
+        }
+
+        (self.fallback)(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpListener;
+
+    // `Request::from_stream` takes a real `TcpStream`, so these tests write the raw request
+    // bytes over an actual loopback connection rather than constructing a `Request` by hand.
+    fn request_from_bytes(raw: &[u8]) -> io::Result<Request> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(raw).unwrap();
+        drop(client); // close so from_stream sees EOF once it's read everything we sent
+
+        let (mut server, _) = listener.accept().unwrap();
+        Request::from_stream(&mut server)
+    }
+
+    #[test]
+    fn parses_request_line_query_and_headers() {
+        let request = request_from_bytes(b"GET /foo?bar=1 HTTP/1.1\r\nHost: example.com\r\n\r\n").unwrap();
+
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/foo");
+        assert_eq!(request.query, "bar=1");
+        assert_eq!(request.headers.get("host"), Some(&"example.com".to_string()));
+        assert!(request.body.is_empty());
+    }
+
+    #[test]
+    fn reads_body_per_content_length() {
+        let request = request_from_bytes(b"POST /submit HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello").unwrap();
+
+        assert_eq!(request.body, b"hello");
+    }
+
+    #[test]
+    fn truncated_header_block_does_not_hang() {
+        // the connection closes before a \r\n\r\n ever shows up; from_stream should return
+        // whatever it managed to read instead of blocking forever for more
+        let request = request_from_bytes(b"GET / HTTP/1.1\r\nHost: exa").unwrap();
+
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/");
+    }
+
+    #[test]
+    fn content_length_longer_than_body_sent_is_an_error() {
+        // client claims 100 bytes of body, sends 5, then closes - read_exact should surface
+        // that as an error rather than silently returning a short body
+        let result = request_from_bytes(b"POST /submit HTTP/1.1\r\nContent-Length: 100\r\n\r\nhello");
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn oversized_content_length_is_rejected_before_reading_the_body() {
+        let raw = format!("POST /submit HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY_SIZE + 1);
+
+        let result = request_from_bytes(raw.as_bytes());
+
+        match result {
+            Err(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn oversized_header_block_is_rejected_before_it_grows_unbounded() {
+        // never sends \r\n\r\n at all - without a header-size cap, from_stream's scan loop
+        // would keep growing `raw` for as long as the client kept sending
+        let raw = format!("GET /{} HTTP/1.1\r\n", "a".repeat(MAX_HEADER_SIZE));
+
+        let result = request_from_bytes(raw.as_bytes());
+
+        match result {
+            Err(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+}
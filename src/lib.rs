@@ -1,7 +1,68 @@
 use std::thread;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+pub mod config;
+pub mod http;
+pub mod static_files;
+
+//struct Job;
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+// an arbitrary but generous ceiling - past this a `--threads` value is almost certainly a
+// typo (or an accidental extra zero) rather than something anyone meant to run with
+const MAX_POOL_SIZE: usize = 10_000;
+
+/// Why `ThreadPool::build` refused to build a pool.
+#[derive(Debug)]
+pub enum PoolCreationError {
+    /// `size` was zero - a pool needs at least one worker to do anything.
+    Zero,
+    /// `size` was larger than `MAX_POOL_SIZE`, almost certainly a misconfiguration.
+    TooLarge(usize),
+}
+
+impl std::fmt::Display for PoolCreationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PoolCreationError::Zero => write!(f, "pool size must be greater than zero"),
+            PoolCreationError::TooLarge(size) => {
+                write!(f, "pool size {} is larger than the maximum of {}", size, MAX_POOL_SIZE)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PoolCreationError {}
+
+enum Message { // a wrapper - such that ThreadPool can terminate properly
+    NewJob(Job),
+    Terminate,
+}
+
+// Terminate used to be pushed onto a worker's own local deque on the theory that the
+// local-deque check always runs first in find_task, so a worker would reach its own
+// Terminate before anything else could. But find_task's steal path has no special case for
+// it: it steals blindly from a sibling's local deque front, Terminate included, so another
+// idle worker could steal worker N's Terminate before worker N's own loop got to it -
+// worker N then spins forever with nothing left to find, and ThreadPool::drop hangs
+// joining it. Routing Terminate through a dedicated flag per worker instead of through the
+// shared, stealable deques sidesteps that race entirely.
+type TerminateFlag = Arc<AtomicBool>;
+
+// Every worker used to dequeue from one shared mpsc::Receiver behind Arc<Mutex<..>>, so all
+// workers serialized on a single lock just to pick up their next job - a throughput ceiling
+// under load. A Deque is the building block of the work-stealing redesign below: every
+// worker gets its own, plus there's one more shared by everybody as the global injector.
+type Deque = Mutex<VecDeque<Message>>;
+
+fn new_deque() -> Arc<Deque> {
+    Arc::new(Mutex::new(VecDeque::new()))
+}
 
 // We create a buffer struct (Worker) first before using thread::spawn, becoz spwan expects to get
 // some code as soon as the thread is created
@@ -9,51 +70,130 @@ use std::sync::Mutex;
 // to its thread to run
 struct Worker {
     id: usize,
-    thread: Option<thread::JoinHandle<()>>
+    thread: Option<thread::JoinHandle<()>>,
+    terminate: TerminateFlag, // this worker's own flag; ThreadPool's Drop sets it directly
 }
 
 impl Worker {
-    pub fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Message>>>) -> Worker {
-        let thread = thread::spawn(move || loop {
-            let message = receiver.lock().unwrap().recv().unwrap();
-            //the lock is held during the call to recv, but it is released before the call to job(), allowing multiple requests to be serviced concurrently
+    pub fn new(id: usize, local: Arc<Deque>, injector: Arc<Deque>, locals: Arc<Vec<Arc<Deque>>>) -> Worker {
+        let terminate = Arc::new(AtomicBool::new(false));
+        let thread_terminate = Arc::clone(&terminate);
 
-            match message {
-                Message::NewJob(job) => {
-                    println!("Worker {} got a job; executing.", id);
+        let thread = thread::spawn(move || {
+            let mut idle_rounds = 0u32;
 
-                    job();
-                }
-                Message::Terminate => {
-                    println!("Worker {} was told to terminate.", id);
+            loop {
+                match Worker::find_task(id, &local, &injector, &locals, &thread_terminate) {
+                    Some(Message::NewJob(job)) => {
+                        println!("Worker {} got a job; executing.", id);
+
+                        idle_rounds = 0;
+                        job();
+                    }
+                    Some(Message::Terminate) => {
+                        println!("Worker {} was told to terminate.", id);
 
-                    break;
+                        break;
+                    }
+                    None => {
+                        // local, global and every sibling came up empty this round - back off
+                        // instead of spinning the CPU hot while waiting for new work
+                        idle_rounds = idle_rounds.saturating_add(1);
+                        if idle_rounds < 100 {
+                            thread::yield_now();
+                        } else {
+                            thread::sleep(Duration::from_millis(1));
+                        }
+                    }
                 }
             }
         });
+
         Worker {
             id,
             thread: Some(thread),
+            terminate,
         }
     }
-}
 
-//struct Job; 
-type Job = Box<dyn FnOnce() + Send + 'static>;
+    // The standard find-task order for a work-stealing scheduler: check our own local deque
+    // first (no contention with anyone else), then the global injector (where `execute` drops
+    // new jobs), and only then try to steal from a sibling. Local pops come off the back
+    // (LIFO - the job we just stole or just moved over from the injector is the one we reach
+    // for again first);
+    // stealing takes from the front of a sibling's deque so owner and thief don't fight over
+    // the same end. Only after all three come up empty do we check our own `terminate` flag -
+    // so whatever was already queued still gets drained before this worker actually exits.
+    fn find_task(
+        id: usize,
+        local: &Deque,
+        injector: &Deque,
+        locals: &[Arc<Deque>],
+        terminate: &AtomicBool,
+    ) -> Option<Message> {
+        if let Some(message) = local.lock().unwrap().pop_back() {
+            return Some(message);
+        }
 
-enum Message { // a wrapper - such that ThreadPool can terminate properly
-    NewJob(Job),
-    Terminate,
+        let mut global = injector.lock().unwrap();
+        let message = global.pop_front();
+        if message.is_some() {
+            // move half of what's left over onto our local deque along with it, so the next
+            // few jobs come off our own deque and idle siblings have something to steal
+            let batch_len = global.len() / 2;
+            let batch: Vec<Message> = global.drain(0..batch_len).collect();
+            drop(global);
+            local.lock().unwrap().extend(batch);
+            return message;
+        }
+        drop(global);
+
+        let sibling_count = locals.len();
+        let start = (id + 1) % sibling_count;
+
+        for offset in 0..sibling_count {
+            let victim = (start + offset) % sibling_count;
+            if victim == id {
+                continue;
+            }
+
+            let mut sibling = locals[victim].lock().unwrap();
+            if sibling.is_empty() {
+                continue;
+            }
+
+            // steal half of the victim's oldest work rather than a single job, so we don't
+            // have to go back and steal again right away
+            let steal_count = sibling.len().div_ceil(2);
+            let mut stolen: VecDeque<Message> = sibling.drain(0..steal_count).collect();
+            drop(sibling);
+
+            let message = stolen.pop_front();
+            if !stolen.is_empty() {
+                local.lock().unwrap().extend(stolen);
+            }
+            if message.is_some() {
+                return message;
+            }
+        }
+
+        if terminate.load(Ordering::SeqCst) {
+            return Some(Message::Terminate);
+        }
+
+        None
+    }
 }
 
-// 1. First, create a channel and ThreadPool hold on as sender of channel
-// 2. Worker hold on to receiver of channel
-// 3. Job type (think of an container) to hold closures (to send down to channel)
-// 4. ThreadPool::execute method to send job (with closures) down to channel
-// 5. Worker loop and execute closures of job
+// 1. Create one shared injector deque; ThreadPool holds it to post new work into
+// 2. Each Worker also gets its own local deque plus a handle to every other worker's local
+//    deque, so it can steal when it runs dry
+// 3. Job type (think of an container) to hold closures (to send down to a deque)
+// 4. ThreadPool::execute method to push a job onto the global injector
+// 5. Worker loop: local -> injector -> steal, then execute whatever it found
 pub struct ThreadPool {
-    workers: Vec<Worker>, //receiver
-    sender: mpsc::Sender<Message> //sender - the pool is the sender itself
+    workers: Vec<Worker>,
+    injector: Arc<Deque>,
 }
 
 impl ThreadPool {
@@ -65,39 +205,73 @@ impl ThreadPool {
     ///
     /// The `new` function will panic if the size is zero.// usigned size (64 or 32 bit depending on the CPU arch)
     pub fn new(size: usize) -> ThreadPool {
-        assert!(size > 0);
+        // `new` stays around as the panicking shorthand existing callers already use;
+        // `build` is what anything that can recover from a bad size (e.g. a `--threads 0`
+        // CLI flag) should call instead.
+        ThreadPool::build(size).expect("failed to build ThreadPool")
+    }
+
+    /// Like `new`, but reports a zero or absurdly large `size` as a `PoolCreationError`
+    /// instead of panicking, so a recoverable misconfiguration (e.g. `--threads 0`) doesn't
+    /// have to crash the whole process.
+    pub fn build(size: usize) -> Result<ThreadPool, PoolCreationError> {
+        if size == 0 {
+            return Err(PoolCreationError::Zero);
+        }
+        if size > MAX_POOL_SIZE {
+            return Err(PoolCreationError::TooLarge(size));
+        }
 
-        let (sender, receiver) = mpsc::channel();
-        // mpsc - multiple producer, single consumer
-        // straight off wont work with multiple consumers
-        // this is multiple threads ownerships (one receiver, shared by multiple workers) -
-        // Arc<Mutex<T>>
-        // Arc will let multiple workers own the receiver
-        // Mutex will ensure only one worker gets a job from the receiver as one time
-        let receiver = Arc::new(Mutex::new(receiver));
+        let injector = new_deque();
+
+        // every worker needs to see every local deque (including its own) to steal from
+        // siblings, so build the full set up front and hand each worker the shared Arc
+        let locals: Vec<Arc<Deque>> = (0..size).map(|_| new_deque()).collect();
+        let locals = Arc::new(locals);
 
         let mut workers = Vec::with_capacity(size);
 
         for id in 0..size {
             // create some threads and store them in the vector
-            workers.push(Worker::new(id, Arc::clone(&receiver)));
+            workers.push(Worker::new(id, Arc::clone(&locals[id]), Arc::clone(&injector), Arc::clone(&locals)));
         }
 
-        ThreadPool { workers, sender }
+        Ok(ThreadPool { workers, injector })
     }
 
     // `execute` method, refer to std::thread::spawn
-    // pub fn spawn<F, T>(f: F) -> JoinHandle<T> 
+    // pub fn spawn<F, T>(f: F) -> JoinHandle<T>
    //     where
    //         F: FnOnce() -> T,
    //         F: Send + 'static,
-   //         T: Send + 'static, 
-    pub fn execute<F>(&self, f: F) 
-    where 
+   //         T: Send + 'static,
+    pub fn execute<F>(&self, f: F)
+    where
         F: FnOnce() + Send + 'static {
             let job = Box::new(f);
 
-            self.sender.send(Message::NewJob(job)).unwrap();
+            self.injector.lock().unwrap().push_back(Message::NewJob(job));
+    }
+
+    // `execute` discards whatever `f` returns, so a handler has no way to signal anything
+    // back to whoever called execute (e.g. "this was the /stop route, begin shutdown").
+    // `execute_with_result` wraps `f` in a closure that sends its return value down a fresh
+    // mpsc channel, then boxes *that* wrapper as an ordinary Job - the Worker loop doesn't
+    // need to know or care that a result is being carried back out.
+    pub fn execute_with_result<F, T>(&self, f: F) -> mpsc::Receiver<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        self.execute(move || {
+            // if the caller already dropped result_receiver it no longer wants the
+            // result; that's fine, just ignore the send error instead of unwrapping
+            let _ = result_sender.send(f());
+        });
+
+        result_receiver
     }
 }
 
@@ -109,10 +283,11 @@ impl Drop for ThreadPool {
         // 1. to send terminate message to all workers thread
         // 2. to join on all worker's thread
         //
-        for _ in &self.workers {
-            // send terminate message to break idling workers' loop
-            // since this is in channel; this can be out of order (unblocking)
-            self.sender.send(Message::Terminate).unwrap();
+        // each worker's Terminate goes out-of-band through its own `terminate` flag rather
+        // than through any shared deque, so stealing can't hand it to the wrong worker (see
+        // the comment on TerminateFlag above)
+        for worker in &self.workers {
+            worker.terminate.store(true, Ordering::SeqCst);
         }
 
         println!("Shutting down all workers.");
@@ -135,3 +310,65 @@ impl Drop for ThreadPool {
         // on forever (deadlock)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_rejects_zero_threads() {
+        let result = ThreadPool::build(0);
+
+        assert!(matches!(result, Err(PoolCreationError::Zero)));
+    }
+
+    #[test]
+    fn build_rejects_too_many_threads() {
+        let result = ThreadPool::build(MAX_POOL_SIZE + 1);
+
+        assert!(matches!(result, Err(PoolCreationError::TooLarge(size)) if size == MAX_POOL_SIZE + 1));
+    }
+
+    #[test]
+    fn build_accepts_a_reasonable_size() {
+        let pool = ThreadPool::build(2);
+
+        assert!(pool.is_ok());
+    }
+
+    #[test]
+    fn every_submitted_job_runs_across_multiple_workers() {
+        // more jobs than workers, so local-deque overflow and stealing both get exercised
+        let pool = ThreadPool::build(4).unwrap();
+        let counter = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+        let receivers: Vec<_> = (0..20)
+            .map(|_| {
+                let counter = Arc::clone(&counter);
+                pool.execute_with_result(move || {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+
+        for receiver in receivers {
+            receiver.recv().unwrap();
+        }
+
+        assert_eq!(counter.load(Ordering::SeqCst), 20);
+    }
+
+    #[test]
+    fn dropping_the_pool_joins_every_worker_instead_of_hanging() {
+        // regression test for the termination race TerminateFlag replaced: if a worker's
+        // Terminate were still routed through a stealable deque, a sibling could steal it and
+        // this drop would block forever joining the worker that never got told to stop
+        let pool = ThreadPool::build(4).unwrap();
+
+        for _ in 0..10 {
+            pool.execute(|| {});
+        }
+
+        drop(pool);
+    }
+}
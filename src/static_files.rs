@@ -0,0 +1,108 @@
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::http::{Request, Response};
+
+// `handle_connection` used to `fs::read_to_string(filename).unwrap()` a single hardcoded
+// filename, which panicked the whole connection on a missing file and couldn't serve
+// anything that wasn't valid UTF-8 (images, CSS, ...). `serve` instead reads raw bytes,
+// derives `Content-Type` from the extension, and turns IO errors into a proper HTTP
+// response rather than an `unwrap()`.
+
+/// Serve the file at `root` joined with `request.path`, suitable for use as a `Router`
+/// fallback via `Router::set_fallback`.
+pub fn serve(root: &str, request: &Request) -> Response {
+    let relative = Path::new(request.path.trim_start_matches('/'));
+
+    // a request path made of anything but plain, relative segments (`..`, an absolute
+    // path, a Windows drive prefix, ...) could walk the joined path outside of `root` -
+    // e.g. `GET /../../etc/passwd` - so refuse it instead of handing it to `fs::read`
+    if !is_contained(relative) {
+        return Response::not_found();
+    }
+
+    let path: PathBuf = Path::new(root).join(relative);
+
+    match fs::read(&path) {
+        Ok(contents) => Response::ok(contents).with_header("Content-Type", mime_type_for(&path)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Response::not_found(),
+        Err(_) => Response::internal_error(),
+    }
+}
+
+/// True if every component of `path` is a plain path segment (`Component::Normal`) - i.e.
+/// it has no `..`, no leading `/`, and no drive prefix, so joining it onto `root` can't
+/// escape `root`.
+fn is_contained(path: &Path) -> bool {
+    path.components().all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Maps a file extension to a `Content-Type`; anything unrecognized falls back to a generic
+/// binary type rather than guessing.
+fn mime_type_for(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("html") | Some("htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "application/javascript; charset=utf-8",
+        Some("json") => "application/json; charset=utf-8",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        Some("ico") => "image/x-icon",
+        Some("txt") => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn request(path: &str) -> Request {
+        Request {
+            method: "GET".to_string(),
+            path: path.to_string(),
+            query: String::new(),
+            headers: HashMap::new(),
+            body: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn traversal_attempt_is_rejected() {
+        let response = serve(".", &request("/../../../../etc/passwd"));
+
+        assert_eq!(response.status_line, "HTTP/1.1 404 NOT FOUND");
+    }
+
+    #[test]
+    fn traversal_attempt_not_at_the_start_is_also_rejected() {
+        let response = serve(".", &request("/foo/../../bar"));
+
+        assert_eq!(response.status_line, "HTTP/1.1 404 NOT FOUND");
+    }
+
+    #[test]
+    fn serves_a_file_that_exists_under_root() {
+        let dir = std::env::temp_dir().join(format!("rust_webserver_static_files_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("hello.txt"), b"hi").unwrap();
+
+        let response = serve(dir.to_str().unwrap(), &request("/hello.txt"));
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(response.status_line, "HTTP/1.1 200 OK");
+        assert_eq!(response.body, b"hi");
+    }
+
+    #[test]
+    fn missing_file_is_a_404() {
+        let response = serve(".", &request("/does-not-exist.txt"));
+
+        assert_eq!(response.status_line, "HTTP/1.1 404 NOT FOUND");
+    }
+}